@@ -1,12 +1,15 @@
 use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::call::{CallResult, Error as CallError};
 use ic_cdk::management_canister::{
-    http_request as mgmt_http_request, HttpHeader, HttpMethod, HttpRequestArgs, HttpRequestResult,
+    cost_http_request, http_request as mgmt_http_request, HttpHeader, HttpMethod, HttpRequestArgs,
+    HttpRequestResult,
 };
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::{Cell, DefaultMemoryImpl, StableBTreeMap, Storable};
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::time::Duration;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -144,6 +147,13 @@ fn xor_with_canister_id(data: &[u8]) -> Vec<u8> {
 //  Data types with efficient binary Storable implementations
 // ═══════════════════════════════════════════════════════════════════════
 
+// Each message execution traps at 40B instructions; stop well short of that.
+const DEFAULT_MAX_INSTRUCTIONS: u64 = 30_000_000_000;
+// Worst case for one batch of searches: scrape (2) + LLM (1) + 3 searches (2 each)
+// + follow-up LLM (1) + forced search (2) + its LLM call (1) = 13.
+const DEFAULT_MAX_OUTCALLS: u32 = 16;
+const DEFAULT_MAX_PARALLEL_TOOLS: u32 = 3;
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct AgentConfig {
     pub persona: String,
@@ -157,6 +167,14 @@ pub struct AgentConfig {
     pub allowed_callers: Vec<Principal>,
     /// How many messages between automatic context compressions (0 = disabled).
     pub compress_interval: u32,
+    /// Per-request instruction budget for the chat/tool loop (0 = disabled).
+    pub max_instructions: u64,
+    /// Per-request cap on outcalls made by the chat/tool loop (0 = disabled).
+    pub max_outcalls: u32,
+    /// Max independent tool calls executed concurrently (0 or 1 = sequential).
+    pub max_parallel_tools: u32,
+    /// URL POSTed a JSON alert when the LLM provider rejects the API key (empty = disabled).
+    pub alert_webhook: String,
 }
 
 impl Default for AgentConfig {
@@ -172,6 +190,10 @@ impl Default for AgentConfig {
             max_response_bytes: 8192,
             allowed_callers: vec![],
             compress_interval: 4, // compress more often = smaller batches = cheaper + fresher notes
+            max_instructions: DEFAULT_MAX_INSTRUCTIONS,
+            max_outcalls: DEFAULT_MAX_OUTCALLS,
+            max_parallel_tools: DEFAULT_MAX_PARALLEL_TOOLS,
+            alert_webhook: String::new(),
         }
     }
 }
@@ -208,6 +230,11 @@ impl Storable for AgentConfig {
         }
         // compress_interval
         buf.extend_from_slice(&self.compress_interval.to_le_bytes());
+        // execution budget
+        buf.extend_from_slice(&self.max_instructions.to_le_bytes());
+        buf.extend_from_slice(&self.max_outcalls.to_le_bytes());
+        buf.extend_from_slice(&self.max_parallel_tools.to_le_bytes());
+        write_str(&mut buf, &self.alert_webhook);
         Cow::Owned(buf)
     }
 
@@ -256,7 +283,17 @@ impl Storable for AgentConfig {
         }
         // compress_interval (may be absent in old data)
         let compress_interval = if p + 4 <= d.len() { read_u32(d, &mut p) } else { 6 };
-        Self { persona, system_prompt, allowed_tools, api_key, model, api_endpoint, max_context_messages, max_response_bytes, allowed_callers, compress_interval }
+        // execution budget (may be absent in old data)
+        let max_instructions = if p + 8 <= d.len() { read_u64(d, &mut p) } else { DEFAULT_MAX_INSTRUCTIONS };
+        let max_outcalls = if p + 4 <= d.len() { read_u32(d, &mut p) } else { DEFAULT_MAX_OUTCALLS };
+        let max_parallel_tools = if p + 4 <= d.len() { read_u32(d, &mut p) } else { DEFAULT_MAX_PARALLEL_TOOLS };
+        // alert_webhook (may be absent in old data)
+        let alert_webhook = if p + 4 <= d.len() { read_str(d, &mut p) } else { String::new() };
+        Self {
+            persona, system_prompt, allowed_tools, api_key, model, api_endpoint,
+            max_context_messages, max_response_bytes, allowed_callers, compress_interval,
+            max_instructions, max_outcalls, max_parallel_tools, alert_webhook,
+        }
     }
 
     const BOUND: Bound = Bound::Bounded { max_size: 8192, is_fixed_size: false };
@@ -296,29 +333,115 @@ pub struct Metrics {
     pub total_cycles_spent: u64,
     pub total_messages: u64,
     pub errors: u64,
+    // Provider error classes (subsets of `errors`)
+    pub provider_auth_errors: u64,
+    pub provider_quota_errors: u64,
+    pub provider_rate_limited: u64,
+    pub provider_unavailable: u64,
+    pub provider_forbidden: u64,
 }
 
 impl Storable for Metrics {
     fn to_bytes(&self) -> Cow<'_, [u8]> {
-        let mut buf = Vec::with_capacity(32);
+        let mut buf = Vec::with_capacity(72);
         buf.extend_from_slice(&self.total_calls.to_le_bytes());
         buf.extend_from_slice(&self.total_cycles_spent.to_le_bytes());
         buf.extend_from_slice(&self.total_messages.to_le_bytes());
         buf.extend_from_slice(&self.errors.to_le_bytes());
+        buf.extend_from_slice(&self.provider_auth_errors.to_le_bytes());
+        buf.extend_from_slice(&self.provider_quota_errors.to_le_bytes());
+        buf.extend_from_slice(&self.provider_rate_limited.to_le_bytes());
+        buf.extend_from_slice(&self.provider_unavailable.to_le_bytes());
+        buf.extend_from_slice(&self.provider_forbidden.to_le_bytes());
         Cow::Owned(buf)
     }
 
     fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
         let d = bytes.as_ref();
+        // Provider error counters (absent in old 32-byte data)
+        let extra = |i: usize| if d.len() >= 40 + i * 8 {
+            u64::from_le_bytes(d[32 + i * 8..40 + i * 8].try_into().unwrap())
+        } else { 0 };
         Self {
             total_calls: u64::from_le_bytes(d[0..8].try_into().unwrap()),
             total_cycles_spent: u64::from_le_bytes(d[8..16].try_into().unwrap()),
             total_messages: u64::from_le_bytes(d[16..24].try_into().unwrap()),
             errors: u64::from_le_bytes(d[24..32].try_into().unwrap()),
+            provider_auth_errors: extra(0),
+            provider_quota_errors: extra(1),
+            provider_rate_limited: extra(2),
+            provider_unavailable: extra(3),
+            provider_forbidden: extra(4),
         }
     }
 
-    const BOUND: Bound = Bound::Bounded { max_size: 32, is_fixed_size: true };
+    const BOUND: Bound = Bound::Bounded { max_size: 72, is_fixed_size: false };
+}
+
+/// Per-feature outcall counts and cycle spend, for cost projections.
+/// Each `*_calls`/`*_cycles` pair records every outcall that was sent, failed ones
+/// included, since those are billed too. `search_chats` counts chats that ran at least
+/// one search, so search spend (fallbacks and parallel searches) projects per chat.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct FeatureCosts {
+    pub chats: u64,
+    pub prompt_bytes: u64,
+    pub llm_calls: u64,
+    pub llm_cycles: u64,
+    pub search_calls: u64,
+    pub search_cycles: u64,
+    pub scrape_calls: u64,
+    pub scrape_cycles: u64,
+    pub compress_calls: u64,
+    pub compress_cycles: u64,
+    pub search_chats: u64,
+}
+
+impl Storable for FeatureCosts {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(88);
+        for v in [
+            self.chats, self.prompt_bytes, self.llm_calls, self.llm_cycles,
+            self.search_calls, self.search_cycles, self.scrape_calls, self.scrape_cycles,
+            self.compress_calls, self.compress_cycles, self.search_chats,
+        ] {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let d = bytes.as_ref();
+        let mut p = 0;
+        Self {
+            chats: read_u64(d, &mut p),
+            prompt_bytes: read_u64(d, &mut p),
+            llm_calls: read_u64(d, &mut p),
+            llm_cycles: read_u64(d, &mut p),
+            search_calls: read_u64(d, &mut p),
+            search_cycles: read_u64(d, &mut p),
+            scrape_calls: read_u64(d, &mut p),
+            scrape_cycles: read_u64(d, &mut p),
+            compress_calls: read_u64(d, &mut p),
+            compress_cycles: read_u64(d, &mut p),
+            // search_chats (absent in old 80-byte data)
+            search_chats: if d.len() >= 88 { read_u64(d, &mut p) } else { 0 },
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: 88, is_fixed_size: false };
+}
+
+/// Projected cycle burn and ICP cost for a hypothetical load.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CostProjection {
+    pub cycles_per_chat: u64,
+    pub daily_cycles: u64,
+    pub monthly_cycles: u64,
+    pub daily_icp_e8s: u64,
+    pub monthly_icp_e8s: u64,
+    pub icp_xdr_rate: f64,  // 1 ICP in XDR, from the exchange rate canister
+    pub sample_chats: u64,  // recorded chats the averages are based on (0 = formula estimates)
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -488,6 +611,60 @@ impl Storable for QueuedTask {
     const BOUND: Bound = Bound::Bounded { max_size: 8192, is_fixed_size: false };
 }
 
+/// Guest invite: lets a principal chat a limited number of times until expiry,
+/// without being added to `allowed_callers`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Invite {
+    pub code: String,                   // 32 hex chars
+    pub max_chats: u32,
+    pub chats_used: u32,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub redeemed_by: Option<Principal>, // None until redeemed
+}
+
+impl Invite {
+    fn is_spent(&self, now: u64) -> bool {
+        now >= self.expires_at || self.chats_used >= self.max_chats
+    }
+}
+
+impl Storable for Invite {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(self.code.len() + 64);
+        write_str(&mut buf, &self.code);
+        buf.extend_from_slice(&self.max_chats.to_le_bytes());
+        buf.extend_from_slice(&self.chats_used.to_le_bytes());
+        buf.extend_from_slice(&self.created_at.to_le_bytes());
+        buf.extend_from_slice(&self.expires_at.to_le_bytes());
+        match &self.redeemed_by {
+            Some(p) => {
+                let pb = p.as_slice();
+                buf.push(pb.len() as u8);
+                buf.extend_from_slice(pb);
+            }
+            None => buf.push(0),
+        }
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let d = bytes.as_ref();
+        let mut p = 0;
+        let code = read_str(d, &mut p);
+        let max_chats = read_u32(d, &mut p);
+        let chats_used = read_u32(d, &mut p);
+        let created_at = read_u64(d, &mut p);
+        let expires_at = read_u64(d, &mut p);
+        let plen = d[p] as usize;
+        p += 1;
+        let redeemed_by = if plen == 0 { None } else { Some(Principal::from_slice(&d[p..p + plen])) };
+        Self { code, max_chats, chats_used, created_at, expires_at, redeemed_by }
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: 128, is_fixed_size: false };
+}
+
 // ═══════════════════════════════════════════════════════════════════════
 //  Wallet types — per-user ICP balance + transaction history
 // ═══════════════════════════════════════════════════════════════════════
@@ -900,7 +1077,31 @@ thread_local! {
         StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))))
     );
 
+    // Guest invites: code → invite (MemoryId 13) + redeemed principal → code (MemoryId 14)
+    static INVITES: RefCell<StableBTreeMap<[u8; 16], Invite, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))))
+    );
+    static GUESTS: RefCell<StableBTreeMap<StorablePrincipal, [u8; 16], Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))))
+    );
+
+    // HTTP gateway API token: SHA-224 of the token, all zeros = none issued (MemoryId 15)
+    static API_TOKEN_HASH: RefCell<Cell<[u8; 28], Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))), [0u8; 28])
+            .expect("api token cell init")
+    );
+
+    // Per-feature cycle spend for cost projections (MemoryId 16)
+    static FEATURE_COSTS: RefCell<Cell<FeatureCosts, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))), FeatureCosts::default())
+            .expect("feature costs cell init")
+    );
+
+    // MemoryId 12 is reserved for the legacy API key migration, which is blocked until
+    // VetKey support lands. It was briefly used for a migration timestamp — do not reuse it.
+
     static MSG_COUNTER: RefCell<u64> = RefCell::new(0);
+    static LAST_ALERT_AT: RefCell<u64> = const { RefCell::new(0) };
     static TASK_COUNTER: RefCell<u64> = RefCell::new(0);
 }
 
@@ -939,6 +1140,37 @@ fn require_authorized() -> Result<(), String> {
     }
 }
 
+/// Like `require_authorized`, but also admits guests holding a live invite.
+/// Returns the guest's invite key, or `None` for the owner.
+fn require_chat_access() -> Result<Option<[u8; 16]>, String> {
+    let denied = match require_authorized() {
+        Ok(()) => return Ok(None),
+        Err(e) => e,
+    };
+    let caller = ic_cdk::api::msg_caller();
+    let key = match GUESTS.with(|g| g.borrow().get(&StorablePrincipal(caller))) {
+        Some(k) => k,
+        None => return Err(denied),
+    };
+    let invite = INVITES.with(|i| i.borrow().get(&key))
+        .ok_or("Invite no longer exists")?;
+    if invite.is_spent(ic_cdk::api::time()) {
+        return Err("Invite expired or out of chats".into());
+    }
+    Ok(Some(key))
+}
+
+/// Count one chat against a guest's invite.
+fn consume_guest_chat(key: [u8; 16]) {
+    INVITES.with(|i| {
+        let mut map = i.borrow_mut();
+        if let Some(mut invite) = map.get(&key) {
+            invite.chats_used += 1;
+            map.insert(key, invite);
+        }
+    });
+}
+
 fn bump_metric(f: impl FnOnce(&mut Metrics)) {
     METRICS_STORE.with(|m| {
         let mut cell = m.borrow_mut();
@@ -948,6 +1180,15 @@ fn bump_metric(f: impl FnOnce(&mut Metrics)) {
     });
 }
 
+fn bump_feature(f: impl FnOnce(&mut FeatureCosts)) {
+    FEATURE_COSTS.with(|c| {
+        let mut cell = c.borrow_mut();
+        let mut costs = cell.get().clone();
+        f(&mut costs);
+        let _ = cell.set(costs);
+    });
+}
+
 fn next_msg_id() -> u64 {
     MSG_COUNTER.with(|c| {
         let mut id = c.borrow_mut();
@@ -975,6 +1216,67 @@ fn log_message(role: &str, content: &str) {
 
 const MAX_PROMPT_BYTES: usize = 4096;
 
+// ── Execution guard: per-request instruction + outcall budget ─────────
+
+const BUDGET_NOTE: &str = "[ran out of budget — reply may be incomplete]";
+
+/// Tracks the instructions and outcalls used by one chat request so the
+/// tool loop can stop gracefully instead of trapping at the instruction limit.
+/// Shared by reference across concurrently running tool calls.
+struct ExecGuard {
+    outcalls: std::cell::Cell<u32>,
+    searched: std::cell::Cell<bool>,
+    max_outcalls: u32,
+    max_instructions: u64,
+}
+
+impl ExecGuard {
+    fn new(config: &AgentConfig) -> Self {
+        Self {
+            outcalls: std::cell::Cell::new(0),
+            searched: std::cell::Cell::new(false),
+            max_outcalls: config.max_outcalls,
+            max_instructions: config.max_instructions,
+        }
+    }
+
+    /// Fail if the current message has used up its instruction budget.
+    /// The counter restarts after every await, matching the per-message trap limit,
+    /// so call this after CPU-heavy work (parsing, truncation) as well as before outcalls.
+    fn check_instructions(&self) -> Result<(), String> {
+        let used = ic_cdk::api::performance_counter(0);
+        if self.max_instructions > 0 && used >= self.max_instructions {
+            return Err(format!("instruction budget exhausted ({} of {})", used, self.max_instructions));
+        }
+        Ok(())
+    }
+
+    /// Reserve one outcall. Fails once either budget is spent.
+    fn charge(&self) -> Result<(), String> {
+        self.check_instructions()?;
+        let outcalls = self.outcalls.get();
+        if self.max_outcalls > 0 && outcalls >= self.max_outcalls {
+            return Err(format!("outcall budget exhausted ({} of {})", outcalls, self.max_outcalls));
+        }
+        self.outcalls.set(outcalls + 1);
+        Ok(())
+    }
+}
+
+/// Return a partial reply with the out-of-budget note appended.
+/// Owner replies are logged; guest replies are not.
+fn over_budget_reply(partial: &str, guest: bool) -> String {
+    let reply = if partial.is_empty() {
+        BUDGET_NOTE.to_string()
+    } else {
+        format!("{}\n\n{}", partial, BUDGET_NOTE)
+    };
+    if !guest {
+        log_message("assistant", &reply);
+    }
+    reply
+}
+
 // PicoState tier budget constants (total: ~2000 chars ~= 650 tokens ~= 2 KB)
 const LAST_REPLY_MAX_CHARS: usize = 300;  // Truncate last assistant reply for continuity
 const MAX_IDENTITY_CHARS: usize = 256;    // I: permanent KV facts (never decay)
@@ -1133,6 +1435,29 @@ fn extract_intel_facts(body: &[u8]) -> Option<String> {
     }
 }
 
+/// Make an HTTP outcall and record what it cost in `total_cycles_spent` and, via `record`,
+/// in the feature's counters. The cost is the price attached to this call minus the refund
+/// on its own response — diffing the canister balance would also pick up other outcalls in
+/// flight (concurrent tool searches). Calls that fail after being sent are billed, so they
+/// are recorded too; calls that were never sent are not.
+async fn metered_http_request(
+    request: &HttpRequestArgs,
+    record: impl FnOnce(&mut FeatureCosts, u64),
+) -> CallResult<HttpRequestResult> {
+    let attached = cost_http_request(request);
+    let result = mgmt_http_request(request).await;
+    let sent = !matches!(
+        result,
+        Err(CallError::InsufficientLiquidCycleBalance(_)) | Err(CallError::CallPerformFailed(_))
+    );
+    if sent {
+        let spent = attached.saturating_sub(ic_cdk::api::msg_cycles_refunded()) as u64;
+        bump_metric(|m| m.total_cycles_spent += spent);
+        bump_feature(|f| record(f, spent));
+    }
+    result
+}
+
 /// Search via SmartSUI server (stealth scraping + AI fact compression).
 async fn pico_search_server(query: &str) -> Result<String, String> {
     let body_str = format!(
@@ -1152,11 +1477,8 @@ async fn pico_search_server(query: &str) -> Result<String, String> {
         is_replicated: Some(false),
     };
     bump_metric(|m| m.total_calls += 1);
-    let bal_before = ic_cdk::api::canister_cycle_balance();
-    let response = mgmt_http_request(&request).await
+    let response = metered_http_request(&request, |f, spent| { f.search_calls += 1; f.search_cycles += spent; }).await
         .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Server search failed: {:?}", e) })?;
-    let bal_after = ic_cdk::api::canister_cycle_balance();
-    bump_metric(|m| m.total_cycles_spent += bal_before.saturating_sub(bal_after) as u64);
 
     extract_intel_facts(&response.body)
         .ok_or_else(|| "No facts in server response".into())
@@ -1181,11 +1503,8 @@ async fn pico_browse_server(target_url: &str) -> Result<String, String> {
         is_replicated: Some(false),
     };
     bump_metric(|m| m.total_calls += 1);
-    let bal_before = ic_cdk::api::canister_cycle_balance();
-    let response = mgmt_http_request(&request).await
+    let response = metered_http_request(&request, |f, spent| { f.scrape_calls += 1; f.scrape_cycles += spent; }).await
         .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Server browse failed: {:?}", e) })?;
-    let bal_after = ic_cdk::api::canister_cycle_balance();
-    bump_metric(|m| m.total_cycles_spent += bal_before.saturating_sub(bal_after) as u64);
 
     extract_intel_facts(&response.body)
         .ok_or_else(|| "No content in server response".into())
@@ -1206,21 +1525,22 @@ async fn pico_scrape_jina(target_url: &str) -> Result<String, String> {
         is_replicated: Some(false),
     };
     bump_metric(|m| m.total_calls += 1);
-    let bal_before = ic_cdk::api::canister_cycle_balance();
-    let response = mgmt_http_request(&request).await
+    let response = metered_http_request(&request, |f, spent| { f.scrape_calls += 1; f.scrape_cycles += spent; }).await
         .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Scrape failed: {:?}", e) })?;
-    let bal_after = ic_cdk::api::canister_cycle_balance();
-    bump_metric(|m| m.total_cycles_spent += bal_before.saturating_sub(bal_after) as u64);
 
     String::from_utf8(response.body)
         .map_err(|_| "Error decoding scraped content".into())
 }
 
 /// Scrape a URL: try server first, fallback to Jina.
-async fn pico_scrape(target_url: &str) -> Result<String, String> {
+async fn pico_scrape(target_url: &str, guard: &ExecGuard) -> Result<String, String> {
+    guard.charge()?;
     match pico_browse_server(target_url).await {
         Ok(content) if !content.is_empty() => Ok(content),
-        _ => pico_scrape_jina(target_url).await,
+        _ => {
+            guard.charge()?;
+            pico_scrape_jina(target_url).await
+        }
     }
 }
 
@@ -1229,51 +1549,72 @@ fn has_tool_call(body: &[u8]) -> bool {
     std::str::from_utf8(body).map(|s| s.contains("\"tool_calls\"")).unwrap_or(false)
 }
 
-/// Extract tool_call ID and search query from the LLM response.
-/// Returns (tool_call_id, query). Handles string and object argument formats.
-fn extract_tool_call(body: &[u8]) -> Option<(String, String)> {
-    let s = std::str::from_utf8(body).ok()?;
-
-    // Extract tool_call id (needed for proper tool result message)
-    let id = extract_json_string_field(s, "\"id\":")
-        .unwrap_or_else(|| "call_0".to_string());
-
-    // Extract arguments (could be string or object)
-    let args_needle = "\"arguments\":";
-    let args_pos = s.find(args_needle)? + args_needle.len();
-    let rest = s[args_pos..].trim_start();
+/// One parsed entry from a tool_calls array.
+struct ToolCall {
+    name: String,
+    args: String, // unescaped arguments JSON
+}
 
-    let args_str = if rest.starts_with('"') {
+/// Parse a tool call's `arguments` value (string or raw object) at the start of `rest`.
+/// Returns the unescaped JSON and the number of bytes consumed.
+fn parse_tool_args(rest: &str) -> Option<(String, usize)> {
+    if let Some(inner) = rest.strip_prefix('"') {
         // String format: "{\"query\":\"...\"}" — unescape
-        let inner = &rest[1..];
         let mut out = String::new();
-        let mut chars = inner.chars();
+        let mut chars = inner.char_indices();
         loop {
             match chars.next()? {
-                '"' => break,
-                '\\' => match chars.next()? {
+                (i, '"') => return Some((out, i + 2)),
+                (_, '\\') => match chars.next()?.1 {
                     '"' => out.push('"'),
                     '\\' => out.push('\\'),
                     'n' => out.push('\n'),
                     c => { out.push('\\'); out.push(c); }
                 },
-                c => out.push(c),
+                (_, c) => out.push(c),
             }
         }
-        out
     } else {
-        // Raw object format: {"query":"..."} — take until closing }
-        let end = rest.find('}').unwrap_or(rest.len());
-        rest[..=end].to_string()
-    };
+        // Raw object — find matching closing brace
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => { depth -= 1; if depth == 0 { return Some((rest[..=i].to_string(), i + 1)); } }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Extract every tool call (name + arguments) from the LLM response, in order.
+fn extract_tool_calls(body: &[u8]) -> Vec<ToolCall> {
+    let mut calls = Vec::new();
+    let s = match std::str::from_utf8(body) { Ok(s) => s, Err(_) => return calls };
+    let mut pos = match s.find("\"tool_calls\"") { Some(p) => p, None => return calls };
+    while let Some(off) = s[pos..].find("\"name\":") {
+        let name_at = pos + off;
+        let name = match extract_json_string_field(&s[name_at..], "\"name\":") { Some(n) => n, None => break };
+        let args_at = match s[name_at..].find("\"arguments\":") { Some(p) => name_at + p + 12, None => break };
+        let rest = s[args_at..].trim_start();
+        let skipped = s.len() - args_at - rest.len();
+        let (args, used) = match parse_tool_args(rest) { Some(a) => a, None => break };
+        calls.push(ToolCall { name, args });
+        pos = args_at + skipped + used;
+    }
+    calls
+}
 
+/// Extract the search query from web_search arguments.
+fn tool_query(args: &str) -> Option<String> {
     // Try "query":"<value>" and "query": "<value>"
     for needle in &["\"query\":\"", "\"query\": \""] {
-        if let Some(qstart) = args_str.find(needle) {
-            let after = &args_str[qstart + needle.len()..];
+        if let Some(qstart) = args.find(needle) {
+            let after = &args[qstart + needle.len()..];
             let qend = after.find('"').unwrap_or(after.len());
             let q = &after[..qend];
-            if !q.is_empty() { return Some((id, q.to_string())); }
+            if !q.is_empty() { return Some(q.to_string()); }
         }
     }
     None
@@ -1290,60 +1631,12 @@ fn extract_json_string_field(s: &str, needle: &str) -> Option<String> {
 }
 
 
-/// Extract the tool name from a tool_calls response.
-fn extract_tool_name(body: &[u8]) -> Option<String> {
-    let s = std::str::from_utf8(body).ok()?;
-    // Look for "name":"<tool_name>" inside tool_calls
-    let tc_pos = s.find("\"tool_calls\"")?;
-    let after_tc = &s[tc_pos..];
-    extract_json_string_field(after_tc, "\"name\":")
-}
-
-/// Extract swap arguments from a tool_calls response.
+/// Extract swap arguments from token_swap arguments.
 /// Returns (pay_symbol, pay_amount, receive_symbol).
-fn extract_swap_args(body: &[u8]) -> Option<(String, String, String)> {
-    let s = std::str::from_utf8(body).ok()?;
-
-    // Extract arguments string (could be string or object)
-    let args_needle = "\"arguments\":";
-    let args_pos = s.find(args_needle)? + args_needle.len();
-    let rest = s[args_pos..].trim_start();
-
-    let args_str = if rest.starts_with('"') {
-        // String format: "{\"pay_symbol\":\"ICP\",...}" — unescape
-        let inner = &rest[1..];
-        let mut out = String::new();
-        let mut chars = inner.chars();
-        loop {
-            match chars.next()? {
-                '"' => break,
-                '\\' => match chars.next()? {
-                    '"' => out.push('"'),
-                    '\\' => out.push('\\'),
-                    'n' => out.push('\n'),
-                    c => { out.push('\\'); out.push(c); }
-                },
-                c => out.push(c),
-            }
-        }
-        out
-    } else {
-        // Raw object — find matching closing brace
-        let mut depth = 0;
-        let mut end = 0;
-        for (i, c) in rest.char_indices() {
-            match c {
-                '{' => depth += 1,
-                '}' => { depth -= 1; if depth == 0 { end = i; break; } }
-                _ => {}
-            }
-        }
-        rest[..=end].to_string()
-    };
-
-    let pay_symbol = extract_json_string_field(&args_str, "\"pay_symbol\":")?;
-    let pay_amount = extract_json_string_field(&args_str, "\"pay_amount\":")?;
-    let receive_symbol = extract_json_string_field(&args_str, "\"receive_symbol\":")?;
+fn tool_swap_args(args: &str) -> Option<(String, String, String)> {
+    let pay_symbol = extract_json_string_field(args, "\"pay_symbol\":")?;
+    let pay_amount = extract_json_string_field(args, "\"pay_amount\":")?;
+    let receive_symbol = extract_json_string_field(args, "\"receive_symbol\":")?;
     Some((pay_symbol, pay_amount, receive_symbol))
 }
 
@@ -1371,10 +1664,18 @@ fn is_search_refusal(reply: &str) -> bool {
 }
 
 /// Search via SmartSUI server first, fallback to Google News RSS.
-async fn pico_search(query: &str) -> Result<String, String> {
+async fn pico_search(query: &str, guard: &ExecGuard) -> Result<String, String> {
+    guard.charge()?;
+    // One guard per chat — count the chat once however many searches it runs
+    if !guard.searched.replace(true) {
+        bump_feature(|f| f.search_chats += 1);
+    }
     match pico_search_server(query).await {
         Ok(facts) if !facts.is_empty() && facts.len() > 20 => Ok(facts),
-        _ => pico_search_rss(query).await,
+        _ => {
+            guard.charge()?;
+            pico_search_rss(query).await
+        }
     }
 }
 
@@ -1402,11 +1703,8 @@ async fn pico_search_rss(query: &str) -> Result<String, String> {
         is_replicated: Some(false),
     };
     bump_metric(|m| m.total_calls += 1);
-    let bal_before = ic_cdk::api::canister_cycle_balance();
-    let response = mgmt_http_request(&request).await
+    let response = metered_http_request(&request, |f, spent| { f.search_calls += 1; f.search_cycles += spent; }).await
         .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Search failed: {:?}", e) })?;
-    let bal_after = ic_cdk::api::canister_cycle_balance();
-    bump_metric(|m| m.total_cycles_spent += bal_before.saturating_sub(bal_after) as u64);
 
     let xml = String::from_utf8(response.body)
         .map_err(|_| String::from("Error decoding search results"))?;
@@ -1430,6 +1728,70 @@ async fn pico_search_rss(query: &str) -> Result<String, String> {
     Ok(results)
 }
 
+/// Execute one tool call. Returns its result section for the follow-up prompt and a
+/// one-line summary shown to the user if the follow-up cannot be completed.
+/// Guests cannot swap from the bot wallet and their searches are not kept in web memory.
+async fn run_tool_call(call: &ToolCall, fallback_query: &str, guard: &ExecGuard, guest: bool) -> (String, String) {
+    if call.name == "token_swap" {
+        if guest {
+            let result = "Swap skipped: not available to guests".to_string();
+            return (format!("[Swap result]\n{}", result), result);
+        }
+        let result = match tool_swap_args(&call.args) {
+            Some((pay_sym, pay_amt, recv_sym)) => match guard.charge() {
+                Ok(()) => match swap_execute(pay_sym, pay_amt, recv_sym).await {
+                    Ok(msg) => format!("Swap successful: {}", msg),
+                    Err(e) => format!("Swap failed: {}", e),
+                },
+                Err(e) => format!("Swap skipped: {}", e),
+            },
+            None => "Could not parse swap arguments from tool call".to_string(),
+        };
+        return (format!("[Swap result]\n{}", result), result);
+    }
+
+    // web_search (default)
+    let query = tool_query(&call.args).unwrap_or_else(|| fallback_query.to_string());
+    let label: String = query.chars().take(60).collect();
+    let result = match pico_search(&query, guard).await {
+        Ok(results) => {
+            if !guest {
+                store_web_entry(&format!("search: {}", label), &results);
+            }
+            results.chars().take(6000).collect::<String>()
+        }
+        Err(e) => format!("Search failed: {}", e),
+    };
+    if let Err(e) = guard.check_instructions() {
+        return (
+            format!("[Search results for: {}]\nSearch skipped: {}", query, e),
+            format!("Search skipped for: {}", label),
+        );
+    }
+    (format!("[Search results for: {}]\n{}", query, result), format!("Search completed for: {}", label))
+}
+
+/// Execute tool calls, running consecutive independent calls (searches) concurrently
+/// in batches of up to `max_parallel`. Swaps change wallet state, so each runs alone.
+/// Results are returned in call order.
+async fn run_tool_calls(calls: &[ToolCall], fallback_query: &str, guard: &ExecGuard, max_parallel: u32, guest: bool) -> Vec<(String, String)> {
+    let cap = max_parallel.max(1) as usize;
+    let mut results = Vec::with_capacity(calls.len());
+    let mut start = 0;
+    while start < calls.len() {
+        let mut end = start + 1;
+        if calls[start].name != "token_swap" {
+            while end < calls.len() && end - start < cap && calls[end].name != "token_swap" {
+                end += 1;
+            }
+        }
+        let batch = calls[start..end].iter().map(|c| run_tool_call(c, fallback_query, guard, guest));
+        results.extend(futures::future::join_all(batch).await);
+        start = end;
+    }
+    results
+}
+
 fn store_web_entry(url: &str, content: &str) {
     let idx = WEB_COUNTER.with(|c| {
         let mut cell = c.borrow_mut();
@@ -1446,11 +1808,132 @@ fn store_web_entry(url: &str, content: &str) {
     WEB_MEM.with(|m| m.borrow_mut().insert(idx, entry));
 }
 
+// ── Provider error mapping ───────────────────────────────────────────
+
+const ALERT_COOLDOWN_NS: u64 = 600_000_000_000; // at most one auth alert per 10 minutes
+
+/// Common failure shapes from OpenAI-compatible, Chutes, and Anthropic APIs.
+#[derive(Debug, PartialEq)]
+enum ProviderError {
+    Auth,        // bad or revoked API key
+    Forbidden,   // key is valid but lacks access to the model or endpoint
+    Quota,       // out of credits / billing limit
+    RateLimited, // too many requests
+    Unavailable, // provider overloaded or down
+    Other(u64),
+}
+
+impl ProviderError {
+    fn classify(status: u64, body: &[u8]) -> Self {
+        let lower = String::from_utf8_lossy(body).to_lowercase();
+        // Quota first: OpenAI reports exhausted quota as 429 insufficient_quota
+        if status == 402
+            || lower.contains("insufficient_quota")
+            || lower.contains("credit balance is too low")
+        {
+            ProviderError::Quota
+        } else if status == 401
+            || lower.contains("invalid_api_key")
+            || lower.contains("authentication_error")
+        {
+            ProviderError::Auth
+        } else if status == 403 || lower.contains("permission_error") {
+            ProviderError::Forbidden
+        } else if status == 429 || lower.contains("rate_limit") {
+            ProviderError::RateLimited
+        } else if status >= 500 || lower.contains("overloaded_error") {
+            ProviderError::Unavailable
+        } else {
+            ProviderError::Other(status)
+        }
+    }
+
+    fn user_message(&self) -> String {
+        match self {
+            ProviderError::Auth => "LLM provider rejected the API key — admin action needed".into(),
+            ProviderError::Forbidden => "LLM provider denied access to this model — admin action needed".into(),
+            ProviderError::Quota => "LLM provider quota exhausted — admin action needed".into(),
+            ProviderError::RateLimited => "LLM provider is rate limiting requests — try again shortly".into(),
+            ProviderError::Unavailable => "LLM provider is temporarily unavailable — try again shortly".into(),
+            ProviderError::Other(status) => format!("LLM provider error ({})", status),
+        }
+    }
+}
+
+/// Classify a non-2xx provider response, count it, alert on auth failures,
+/// and return a user-facing message instead of the raw body.
+fn provider_error(status: u64, body: &[u8]) -> String {
+    let err = ProviderError::classify(status, body);
+    bump_metric(|m| {
+        m.errors += 1;
+        match err {
+            ProviderError::Auth => m.provider_auth_errors += 1,
+            ProviderError::Forbidden => m.provider_forbidden += 1,
+            ProviderError::Quota => m.provider_quota_errors += 1,
+            ProviderError::RateLimited => m.provider_rate_limited += 1,
+            ProviderError::Unavailable => m.provider_unavailable += 1,
+            ProviderError::Other(_) => {}
+        }
+    });
+    if err == ProviderError::Auth {
+        ic_cdk::futures::spawn(send_auth_alert(status));
+    }
+    match err {
+        ProviderError::Other(_) => {
+            let snippet: String = String::from_utf8_lossy(body).chars().take(200).collect();
+            format!("{}: {}", err.user_message(), snippet)
+        }
+        _ => err.user_message(),
+    }
+}
+
+/// POST an auth-failure alert to the configured webhook (rate-limited, best effort).
+async fn send_auth_alert(status: u64) {
+    let config = get_config();
+    if config.alert_webhook.is_empty() {
+        return;
+    }
+    let now = ic_cdk::api::time();
+    let recent = LAST_ALERT_AT.with(|t| {
+        let mut last = t.borrow_mut();
+        if *last != 0 && now.saturating_sub(*last) < ALERT_COOLDOWN_NS {
+            return true;
+        }
+        *last = now;
+        false
+    });
+    if recent {
+        return;
+    }
+    let body_str = format!(
+        r#"{{"alert":"provider_auth_failed","status":{},"endpoint":"{}","model":"{}","canister":"{}"}}"#,
+        status,
+        json_escape(&config.api_endpoint),
+        json_escape(&config.model),
+        ic_cdk::api::canister_self()
+    );
+    let request = HttpRequestArgs {
+        url: config.alert_webhook,
+        method: HttpMethod::POST,
+        body: Some(body_str.into_bytes()),
+        max_response_bytes: Some(1_000),
+        transform: None,
+        headers: vec![
+            HttpHeader { name: "Content-Type".into(), value: "application/json".into() },
+        ],
+        is_replicated: Some(false),
+    };
+    bump_metric(|m| m.total_calls += 1);
+    let _ = mgmt_http_request(&request).await;
+}
+
 /// Build the ultra-compressed messages array.  Exactly 2-3 JSON messages:
 ///   1. system prompt + structured PicoState (I:/T:/E:/P: tiers)
 ///   2. last assistant reply, truncated (for reference continuity) — optional
 ///   3. current user prompt
-fn build_messages_json(config: &AgentConfig, prompt: &str) -> String {
+///
+/// With `with_memory` false (guest chats) the PicoState, web memory and last reply are left out.
+fn build_messages_json(config: &AgentConfig, prompt: &str, with_memory: bool) -> String {
     let mut json = String::with_capacity(4096);
     json.push('[');
 
@@ -1466,8 +1949,8 @@ fn build_messages_json(config: &AgentConfig, prompt: &str) -> String {
     json.push_str("{\"role\":\"system\",\"content\":\"");
     json.push_str(&json_escape(&sys_prompt));
 
-    let has_state = !state.identity.is_empty() || !state.thread.is_empty()
-        || !state.episodes.is_empty() || !state.priors.is_empty();
+    let has_state = with_memory && (!state.identity.is_empty() || !state.thread.is_empty()
+        || !state.episodes.is_empty() || !state.priors.is_empty());
     if has_state {
         json.push_str("\\n\\n[M]\\n");
         if !state.identity.is_empty() {
@@ -1498,7 +1981,7 @@ fn build_messages_json(config: &AgentConfig, prompt: &str) -> String {
         entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
         entries
     });
-    if !web_entries.is_empty() {
+    if with_memory && !web_entries.is_empty() {
         json.push_str("\\n\\n[W] Recent lookups:\\n");
         let now = ic_cdk::api::time();
         for (i, entry) in web_entries.iter().enumerate() {
@@ -1520,7 +2003,7 @@ fn build_messages_json(config: &AgentConfig, prompt: &str) -> String {
     json.push_str("\"}");
 
     // ── message 2 (optional): last assistant reply, truncated for continuity ──
-    if config.max_context_messages > 0 {
+    if with_memory && config.max_context_messages > 0 {
         let counter = MSG_COUNTER.with(|c| *c.borrow());
         let last_asst: Option<String> = CHAT_LOG.with(|c| {
             let map = c.borrow();
@@ -1557,17 +2040,17 @@ fn build_messages_json(config: &AgentConfig, prompt: &str) -> String {
 
 const TOOLS_JSON: &str = r#","tools":[{"type":"function","function":{"name":"web_search","description":"Search the web for current information: news, prices, weather, sports, facts, or anything you need real-time data for. Always use this instead of saying you cannot browse.","parameters":{"type":"object","properties":{"query":{"type":"string","description":"Search query"}},"required":["query"]}}},{"type":"function","function":{"name":"token_swap","description":"Swap tokens on KongSwap DEX using the bot wallet. Supported tokens: ICP, ckUSDC, ckUSDT. Use this when the user asks to swap, trade, or exchange tokens.","parameters":{"type":"object","properties":{"pay_symbol":{"type":"string","description":"Token to sell (e.g. ICP, ckUSDC, ckUSDT)"},"pay_amount":{"type":"string","description":"Amount to sell as a decimal string (e.g. 1.5)"},"receive_symbol":{"type":"string","description":"Token to buy (e.g. ckUSDC, ICP, ckUSDT)"}},"required":["pay_symbol","pay_amount","receive_symbol"]}}}],"tool_choice":"auto""#;
 
-fn build_request_body(config: &AgentConfig, prompt: &str) -> Vec<u8> {
-    build_request_body_inner(config, prompt, true)
+fn build_request_body(config: &AgentConfig, prompt: &str, with_memory: bool) -> Vec<u8> {
+    build_request_body_inner(config, prompt, true, with_memory)
 }
 
-fn build_request_body_no_tools(config: &AgentConfig, prompt: &str) -> Vec<u8> {
-    build_request_body_inner(config, prompt, false)
+fn build_request_body_no_tools(config: &AgentConfig, prompt: &str, with_memory: bool) -> Vec<u8> {
+    build_request_body_inner(config, prompt, false, with_memory)
 }
 
 
-fn build_request_body_inner(config: &AgentConfig, prompt: &str, with_tools: bool) -> Vec<u8> {
-    let messages = build_messages_json(config, prompt);
+fn build_request_body_inner(config: &AgentConfig, prompt: &str, with_tools: bool, with_memory: bool) -> Vec<u8> {
+    let messages = build_messages_json(config, prompt, with_memory);
     let mut body = String::with_capacity(messages.len() + 512);
     body.push_str("{\"model\":\"");
     body.push_str(&json_escape(&config.model));
@@ -1679,25 +2162,17 @@ Rules: no articles, no filler, pipe-delimit facts, abbreviate aggressively. ONLY
     };
 
     bump_metric(|m| m.total_calls += 1);
-    let bal_before = ic_cdk::api::canister_cycle_balance();
-
-    let response = mgmt_http_request(&request).await
+    let response = metered_http_request(&request, |f, spent| { f.compress_calls += 1; f.compress_cycles += spent; }).await
         .map_err(|e| {
             bump_metric(|m| m.errors += 1);
             format!("Compression outcall failed: {:?}", e)
         })?;
 
-    let bal_after = ic_cdk::api::canister_cycle_balance();
-    let actual_spent = bal_before.saturating_sub(bal_after) as u64;
-    bump_metric(|m| m.total_cycles_spent += actual_spent);
-
     // Check HTTP status
     let status = response.status.0.to_u64_digits();
     let status_code = if status.is_empty() { 0u64 } else { status[0] };
     if status_code < 200 || status_code >= 300 {
-        let body_str = String::from_utf8_lossy(&response.body);
-        bump_metric(|m| m.errors += 1);
-        return Err(format!("Compression API error ({}): {}", status_code, body_str));
+        return Err(format!("Compression failed: {}", provider_error(status_code, &response.body)));
     }
 
     let raw = extract_content(&response.body)
@@ -1809,6 +2284,14 @@ fn crc32(data: &[u8]) -> u32 {
     !crc
 }
 
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = std::fmt::Write::write_fmt(&mut hex, format_args!("{:02x}", b));
+    }
+    hex
+}
+
 /// Convert a Principal to an ICP Account ID (default subaccount).
 /// Formula: CRC32(SHA-224("\x0Aaccount-id" + principal_bytes + 32_zero_bytes))
 /// Returns 64-char hex string.
@@ -2375,6 +2858,119 @@ fn get_profile() -> UserProfile {
     USER_PROFILE.with(|p| p.borrow().get().clone())
 }
 
+// ═══════════════════════════════════════════════════════════════════════
+//  Guest invites
+// ═══════════════════════════════════════════════════════════════════════
+
+const MAX_INVITE_SECS: u64 = 30 * 24 * 3600; // 30 days
+const MAX_INVITE_CHATS: u32 = 10_000;
+const INVITE_SWEEP_SECS: u64 = 3600; // hourly
+
+fn invite_code_to_key(code: &str) -> Option<[u8; 16]> {
+    if code.len() != 32 || !code.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; 16];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&code[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+/// Drop expired or exhausted invites and unbind their guests.
+fn sweep_invites() {
+    let now = ic_cdk::api::time();
+    let spent: Vec<([u8; 16], Option<Principal>)> = INVITES.with(|i| {
+        i.borrow().iter()
+            .filter(|(_, inv)| inv.is_spent(now))
+            .map(|(k, inv)| (k, inv.redeemed_by))
+            .collect()
+    });
+    for (key, guest) in spent {
+        INVITES.with(|i| i.borrow_mut().remove(&key));
+        if let Some(p) = guest {
+            GUESTS.with(|g| g.borrow_mut().remove(&StorablePrincipal(p)));
+        }
+    }
+}
+
+/// Sweep invites periodically so expired guests are unbound even when nobody calls in.
+/// Timers do not survive upgrades, so this runs from both `init` and `post_upgrade`.
+fn start_invite_sweep() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(INVITE_SWEEP_SECS), || async {
+        sweep_invites();
+    });
+}
+
+/// Create a single-use invite code good for `max_chats` chats over the next `expires_in_secs`.
+#[ic_cdk::update]
+async fn create_invite(max_chats: u32, expires_in_secs: u64) -> Result<String, String> {
+    require_controller()?;
+    if max_chats == 0 || max_chats > MAX_INVITE_CHATS {
+        return Err(format!("max_chats must be 1-{}", MAX_INVITE_CHATS));
+    }
+    if expires_in_secs == 0 || expires_in_secs > MAX_INVITE_SECS {
+        return Err(format!("Expiry must be 1-{} seconds", MAX_INVITE_SECS));
+    }
+    sweep_invites();
+
+    let rand = ic_cdk::management_canister::raw_rand().await
+        .map_err(|e| format!("Failed to generate invite code: {:?}", e))?;
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&rand[..16]);
+    let code = to_hex(&key);
+
+    let now = ic_cdk::api::time();
+    INVITES.with(|i| {
+        i.borrow_mut().insert(key, Invite {
+            code: code.clone(),
+            max_chats,
+            chats_used: 0,
+            created_at: now,
+            expires_at: now + expires_in_secs * 1_000_000_000,
+            redeemed_by: None,
+        });
+    });
+    Ok(code)
+}
+
+/// Bind the caller to an invite. Each invite can be redeemed by one principal.
+#[ic_cdk::update]
+fn redeem_invite(code: String) -> Result<String, String> {
+    let caller = ic_cdk::api::msg_caller();
+    if caller == Principal::anonymous() {
+        return Err("Anonymous calls not allowed — authenticate with Internet Identity".into());
+    }
+    sweep_invites();
+
+    let key = invite_code_to_key(&code.trim().to_lowercase()).ok_or("Invalid invite code")?;
+    let mut invite = INVITES.with(|i| i.borrow().get(&key))
+        .ok_or("Invite not found or expired")?;
+    match invite.redeemed_by {
+        Some(p) if p == caller => return Ok("Invite already redeemed".into()),
+        Some(_) => return Err("Invite already redeemed".into()),
+        None => {}
+    }
+
+    // A principal holds at most one invite — replace any previous binding
+    if let Some(old) = GUESTS.with(|g| g.borrow().get(&StorablePrincipal(caller))) {
+        INVITES.with(|i| i.borrow_mut().remove(&old));
+    }
+    invite.redeemed_by = Some(caller);
+    let (max_chats, expires_at) = (invite.max_chats, invite.expires_at);
+    INVITES.with(|i| i.borrow_mut().insert(key, invite));
+    GUESTS.with(|g| g.borrow_mut().insert(StorablePrincipal(caller), key));
+
+    let hours_left = expires_at.saturating_sub(ic_cdk::api::time()) / 3_600_000_000_000;
+    Ok(format!("Invite redeemed: {} chats, expires in {}h", max_chats, hours_left))
+}
+
+#[ic_cdk::query]
+fn list_invites() -> Vec<Invite> {
+    require_controller().unwrap_or_else(|e| ic_cdk::trap(&e));
+    INVITES.with(|i| i.borrow().iter().map(|(_, inv)| inv).collect())
+}
+
 // ═══════════════════════════════════════════════════════════════════════
 //  Admin endpoints
 // ═══════════════════════════════════════════════════════════════════════
@@ -2397,6 +2993,9 @@ fn set_api_key(key: String) -> Result<(), String> {
 #[ic_cdk::update]
 fn configure(config: AgentConfig) -> Result<(), String> {
     require_controller()?;
+    if !config.alert_webhook.is_empty() && !config.alert_webhook.starts_with("https://") {
+        return Err("Alert webhook must be an https:// URL".into());
+    }
     CONFIG.with(|c| { let _ = c.borrow_mut().set(config); });
     Ok(())
 }
@@ -2406,6 +3005,10 @@ fn get_config_public() -> AgentConfig {
     CONFIG.with(|c| {
         let mut cfg = c.borrow().get().clone();
         cfg.api_key = cfg.api_key.map(|_| "***".into());
+        // Webhook URLs (Slack, Discord) embed their secret
+        if !cfg.alert_webhook.is_empty() {
+            cfg.alert_webhook = "***".into();
+        }
         cfg
     })
 }
@@ -2461,7 +3064,8 @@ async fn dispatch_dev_task(task_prompt: &str) -> Result<String, String> {
 
 #[ic_cdk::update]
 async fn chat(prompt: String) -> Result<String, String> {
-    require_authorized()?;
+    let guest_key = require_chat_access()?;
+    let guest = guest_key.is_some();
 
     if prompt.len() > MAX_PROMPT_BYTES {
         return Err(format!("Prompt too large: {} bytes (max {})", prompt.len(), MAX_PROMPT_BYTES));
@@ -2469,6 +3073,9 @@ async fn chat(prompt: String) -> Result<String, String> {
 
     // /dev command → dispatch to Hetzner dev agent, skip LLM
     if prompt.starts_with("/dev ") {
+        if guest {
+            return Err("/dev is not available to guests".into());
+        }
         let task = &prompt[5..];
         log_message("user", &prompt);
         let reply = match dispatch_dev_task(task).await {
@@ -2483,15 +3090,25 @@ async fn chat(prompt: String) -> Result<String, String> {
     let api_key = config.api_key.as_deref()
         .ok_or("API key not configured")?.to_string();
 
-    log_message("user", &prompt);
+    // Guests chat without memory: nothing is read from or written to the owner's history
+    match guest_key {
+        Some(key) => consume_guest_chat(key),
+        None => log_message("user", &prompt),
+    }
+    bump_feature(|f| { f.chats += 1; f.prompt_bytes += prompt.len() as u64; });
+
+    // Budget for this request — exhausting it yields a partial reply, not a trap
+    let guard = ExecGuard::new(&config);
 
     // URL in user message? Auto-scrape via Jina Reader before LLM call
     let mut augmented_prompt = prompt.clone();
     if let Some(url) = extract_url(&prompt) {
         let url_owned = url.to_string();
-        match pico_scrape(&url_owned).await {
+        match pico_scrape(&url_owned, &guard).await {
             Ok(content) => {
-                store_web_entry(&url_owned, &content);
+                if !guest {
+                    store_web_entry(&url_owned, &content);
+                }
                 let truncated: String = content.chars().take(6000).collect();
                 augmented_prompt = format!("{}\n\n[Web: {}]\n{}", prompt, url_owned, truncated);
             }
@@ -2499,9 +3116,12 @@ async fn chat(prompt: String) -> Result<String, String> {
                 augmented_prompt = format!("{}\n\n[Web scrape failed: {}]", prompt, e);
             }
         }
+        if guard.check_instructions().is_err() {
+            return Ok(over_budget_reply("", guest));
+        }
     }
 
-    let body = build_request_body(&config, &augmented_prompt);
+    let body = build_request_body(&config, &augmented_prompt, !guest);
 
     // Non-replicated outcall: only 1 subnet node makes the request (no consensus needed)
     let request = HttpRequestArgs {
@@ -2517,109 +3137,61 @@ async fn chat(prompt: String) -> Result<String, String> {
         is_replicated: Some(false),
     };
 
+    if guard.charge().is_err() {
+        return Ok(over_budget_reply("", guest));
+    }
     bump_metric(|m| m.total_calls += 1);
-    let bal_before = ic_cdk::api::canister_cycle_balance();
-
-    let response = mgmt_http_request(&request).await
+    let response = metered_http_request(&request, |f, spent| { f.llm_calls += 1; f.llm_cycles += spent; }).await
         .map_err(|e| {
             bump_metric(|m| m.errors += 1);
             format!("HTTP outcall failed: {:?}", e)
         })?;
 
-    let bal_after = ic_cdk::api::canister_cycle_balance();
-    let actual_spent = bal_before.saturating_sub(bal_after) as u64;
-    bump_metric(|m| m.total_cycles_spent += actual_spent);
-
     // Check HTTP status
     let status = response.status.0.to_u64_digits();
     let status_code = if status.is_empty() { 0u64 } else { status[0] };
     if status_code < 200 || status_code >= 300 {
-        let body_str = String::from_utf8_lossy(&response.body);
-        bump_metric(|m| m.errors += 1);
-        return Err(format!("API error ({}): {}", status_code, body_str));
+        return Err(provider_error(status_code, &response.body));
     }
 
-    // ── Tool loop: detect tool_calls → execute → re-call with result ──
+    // ── Tool loop: detect tool_calls → execute (independent calls concurrently) → re-call with results ──
     let reply;
     if has_tool_call(&response.body) {
-        let tool_name = extract_tool_name(&response.body);
-
-        if tool_name.as_deref() == Some("token_swap") {
-            // ── token_swap tool ──
-            let tool_result = match extract_swap_args(&response.body) {
-                Some((pay_sym, pay_amt, recv_sym)) => {
-                    match swap_execute(pay_sym.clone(), pay_amt.clone(), recv_sym.clone()).await {
-                        Ok(msg) => format!("Swap successful: {}", msg),
-                        Err(e) => format!("Swap failed: {}", e),
-                    }
-                }
-                None => "Could not parse swap arguments from tool call".to_string(),
-            };
-
-            // Re-call LLM with swap result (no tools)
-            let swap_prompt = format!("{}\n\n[Swap result]\n{}", augmented_prompt, tool_result);
-            let body2 = build_request_body_no_tools(&config, &swap_prompt);
-            let req2 = HttpRequestArgs {
-                url: config.api_endpoint.clone(),
-                max_response_bytes: Some(config.max_response_bytes),
-                method: HttpMethod::POST,
-                headers: vec![
-                    HttpHeader { name: "Content-Type".into(), value: "application/json".into() },
-                    HttpHeader { name: "Authorization".into(), value: format!("Bearer {}", api_key) },
-                ],
-                body: Some(body2),
-                transform: None,
-                is_replicated: Some(false),
-            };
-            bump_metric(|m| m.total_calls += 1);
-            let b2 = ic_cdk::api::canister_cycle_balance();
-            let resp2 = mgmt_http_request(&req2).await
-                .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Swap follow-up failed: {:?}", e) })?;
-            let b3 = ic_cdk::api::canister_cycle_balance();
-            bump_metric(|m| m.total_cycles_spent += b2.saturating_sub(b3) as u64);
-            reply = extract_content(&resp2.body)
-                .unwrap_or_else(|| tool_result);
-        } else {
-            // ── web_search tool (default) ──
-            let query = extract_tool_call(&response.body)
-                .map(|(_, q)| q)
-                .unwrap_or_else(|| prompt.clone());
-
-            let tool_result = match pico_search(&query).await {
-                Ok(results) => {
-                    let label: String = query.chars().take(60).collect();
-                    store_web_entry(&format!("search: {}", label), &results);
-                    results.chars().take(6000).collect::<String>()
-                }
-                Err(e) => format!("Search failed: {}", e),
-            };
-
-            // Re-call LLM with search results injected into user prompt (no tools).
-            // Note: proper tool_calls→tool message flow fails on Chutes/DeepSeek,
-            // so we use the simpler approach of augmenting the user message.
-            let search_prompt = format!("{}\n\n[Search results for: {}]\n{}", augmented_prompt, query, tool_result);
-            let body2 = build_request_body_no_tools(&config, &search_prompt);
-            let req2 = HttpRequestArgs {
-                url: config.api_endpoint.clone(),
-                max_response_bytes: Some(config.max_response_bytes),
-                method: HttpMethod::POST,
-                headers: vec![
-                    HttpHeader { name: "Content-Type".into(), value: "application/json".into() },
-                    HttpHeader { name: "Authorization".into(), value: format!("Bearer {}", api_key) },
-                ],
-                body: Some(body2),
-                transform: None,
-                is_replicated: Some(false),
-            };
-            bump_metric(|m| m.total_calls += 1);
-            let b2 = ic_cdk::api::canister_cycle_balance();
-            let resp2 = mgmt_http_request(&req2).await
-                .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Search follow-up failed: {:?}", e) })?;
-            let b3 = ic_cdk::api::canister_cycle_balance();
-            bump_metric(|m| m.total_cycles_spent += b2.saturating_sub(b3) as u64);
-            reply = extract_content(&resp2.body)
-                .unwrap_or_else(|| "Search completed but could not parse follow-up".into());
+        let mut calls = extract_tool_calls(&response.body);
+        if calls.is_empty() {
+            // Unparseable tool call — fall back to searching the user's prompt
+            calls.push(ToolCall { name: "web_search".into(), args: String::new() });
+        }
+        let results = run_tool_calls(&calls, &prompt, &guard, config.max_parallel_tools, guest).await;
+        let (sections, summaries): (Vec<String>, Vec<String>) = results.into_iter().unzip();
+        let tool_result = sections.join("\n\n");
+        let tool_summary = summaries.join("\n");
+
+        // Re-call LLM with tool results injected into user prompt (no tools).
+        // Note: proper tool_calls→tool message flow fails on Chutes/DeepSeek,
+        // so we use the simpler approach of augmenting the user message.
+        let tool_prompt = format!("{}\n\n{}", augmented_prompt, tool_result);
+        let body2 = build_request_body_no_tools(&config, &tool_prompt, !guest);
+        let req2 = HttpRequestArgs {
+            url: config.api_endpoint.clone(),
+            max_response_bytes: Some(config.max_response_bytes),
+            method: HttpMethod::POST,
+            headers: vec![
+                HttpHeader { name: "Content-Type".into(), value: "application/json".into() },
+                HttpHeader { name: "Authorization".into(), value: format!("Bearer {}", api_key) },
+            ],
+            body: Some(body2),
+            transform: None,
+            is_replicated: Some(false),
+        };
+        if guard.charge().is_err() {
+            return Ok(over_budget_reply(&tool_summary, guest));
         }
+        bump_metric(|m| m.total_calls += 1);
+        let resp2 = metered_http_request(&req2, |f, spent| { f.llm_calls += 1; f.llm_cycles += spent; }).await
+            .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Tool follow-up failed: {:?}", e) })?;
+        reply = extract_content(&resp2.body)
+            .unwrap_or_else(|| format!("{}\n\nTools completed but could not parse follow-up", tool_summary));
     } else {
         reply = extract_content(&response.body).ok_or_else(|| {
             bump_metric(|m| m.errors += 1);
@@ -2638,15 +3210,20 @@ async fn chat(prompt: String) -> Result<String, String> {
     // force a search with the user's original prompt and re-call
     let reply = if is_search_refusal(&reply) {
         let query = prompt.clone();
-        match pico_search(&query).await {
+        match pico_search(&query, &guard).await {
             Ok(results) => {
-                let label: String = query.chars().take(60).collect();
-                store_web_entry(&format!("search: {}", label), &results);
+                if !guest {
+                    let label: String = query.chars().take(60).collect();
+                    store_web_entry(&format!("search: {}", label), &results);
+                }
                 let truncated: String = results.chars().take(6000).collect();
+                if guard.check_instructions().is_err() {
+                    return Ok(over_budget_reply(&reply, guest));
+                }
                 let search_prompt = format!(
                     "{}\n\n[Search results for: {}]\n{}", prompt, query, truncated
                 );
-                let body2 = build_request_body_no_tools(&config, &search_prompt);
+                let body2 = build_request_body_no_tools(&config, &search_prompt, !guest);
                 let req2 = HttpRequestArgs {
                     url: config.api_endpoint.clone(),
                     max_response_bytes: Some(config.max_response_bytes),
@@ -2659,12 +3236,12 @@ async fn chat(prompt: String) -> Result<String, String> {
                     transform: None,
                     is_replicated: Some(false),
                 };
+                if guard.charge().is_err() {
+                    return Ok(over_budget_reply(&reply, guest));
+                }
                 bump_metric(|m| m.total_calls += 1);
-                let b2 = ic_cdk::api::canister_cycle_balance();
-                let resp2 = mgmt_http_request(&req2).await
+                let resp2 = metered_http_request(&req2, |f, spent| { f.llm_calls += 1; f.llm_cycles += spent; }).await
                     .map_err(|e| { bump_metric(|m| m.errors += 1); format!("Forced search failed: {:?}", e) })?;
-                let b3 = ic_cdk::api::canister_cycle_balance();
-                bump_metric(|m| m.total_cycles_spent += b2.saturating_sub(b3) as u64);
                 extract_content(&resp2.body).unwrap_or(reply)
             }
             Err(_) => reply, // search failed, return original reply
@@ -2673,6 +3250,10 @@ async fn chat(prompt: String) -> Result<String, String> {
         reply
     };
 
+    if guest {
+        return Ok(reply);
+    }
+
     log_message("assistant", &reply);
 
     if should_compress(&config) {
@@ -2761,7 +3342,8 @@ fn clear_notes() -> Result<(), String> {
 #[ic_cdk::update]
 async fn browse(url: String) -> Result<String, String> {
     require_authorized()?;
-    let content = pico_scrape(&url).await?;
+    let guard = ExecGuard::new(&get_config());
+    let content = pico_scrape(&url, &guard).await?;
     store_web_entry(&url, &content);
     Ok(content.chars().take(500).collect())
 }
@@ -2810,6 +3392,167 @@ fn cycle_balance() -> u128 {
     ic_cdk::api::canister_cycle_balance()
 }
 
+#[ic_cdk::query]
+fn get_feature_costs() -> FeatureCosts {
+    FEATURE_COSTS.with(|c| c.borrow().get().clone())
+}
+
+// ── Cost simulation ─────────────────────────────────────────────────────
+
+const XRC_TEXT: &str = "uf6dk-hyaaa-aaaaq-qaaaq-cai";
+const XRC_FEE_CYCLES: u128 = 1_000_000_000; // cycles attached to each get_exchange_rate call
+
+// HTTPS outcall pricing on a 13-node subnet: base + per request byte + per max response byte
+const OUTCALL_BASE_CYCLES: u64 = 49_140_000;
+const OUTCALL_REQ_BYTE_CYCLES: u64 = 5_200;
+const OUTCALL_RESP_BYTE_CYCLES: u64 = 10_400;
+
+/// Formula estimate for one outcall, used until real averages are recorded.
+fn outcall_cycles(request_bytes: u64, max_response_bytes: u64) -> u64 {
+    OUTCALL_BASE_CYCLES
+        + OUTCALL_REQ_BYTE_CYCLES * request_bytes
+        + OUTCALL_RESP_BYTE_CYCLES * max_response_bytes
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum XrcAssetClass {
+    Cryptocurrency,
+    FiatCurrency,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct XrcAsset {
+    symbol: String,
+    class: XrcAssetClass,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct XrcGetExchangeRateRequest {
+    base_asset: XrcAsset,
+    quote_asset: XrcAsset,
+    timestamp: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct XrcMetadata {
+    decimals: u32,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct XrcExchangeRate {
+    rate: u64,
+    metadata: XrcMetadata,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum XrcError {
+    AnonymousPrincipalNotAllowed,
+    Pending,
+    CryptoBaseAssetNotFound,
+    CryptoQuoteAssetNotFound,
+    StablecoinRateNotFound,
+    StablecoinRateTooFewRates,
+    StablecoinRateZeroRate,
+    ForexInvalidTimestamp,
+    ForexBaseAssetNotFound,
+    ForexQuoteAssetNotFound,
+    ForexAssetsNotFound,
+    RateLimited,
+    NotEnoughCycles,
+    FailedToAcceptCycles,
+    InconsistentRatesReceived,
+    Other { code: u32, description: String },
+}
+
+/// Fetch the ICP/XDR rate (1 XDR = 1T cycles) from the exchange rate canister.
+/// Returns (rate, decimals).
+async fn fetch_icp_xdr_rate() -> Result<(u64, u32), String> {
+    let xrc = Principal::from_text(XRC_TEXT).unwrap();
+    let args = XrcGetExchangeRateRequest {
+        base_asset: XrcAsset { symbol: "ICP".into(), class: XrcAssetClass::Cryptocurrency },
+        quote_asset: XrcAsset { symbol: "CXDR".into(), class: XrcAssetClass::FiatCurrency },
+        timestamp: None,
+    };
+    let response = ic_cdk::call::Call::unbounded_wait(xrc, "get_exchange_rate")
+        .with_arg(&args)
+        .with_cycles(XRC_FEE_CYCLES)
+        .await
+        .map_err(|e| format!("Exchange rate call failed: {:?}", e))?;
+    let result: Result<XrcExchangeRate, XrcError> = response.candid()
+        .map_err(|e| format!("Exchange rate decode failed: {:?}", e))?;
+    match result {
+        Ok(r) if r.rate > 0 => Ok((r.rate, r.metadata.decimals)),
+        Ok(_) => Err("Exchange rate canister returned a zero rate".into()),
+        Err(e) => Err(format!("Exchange rate error: {:?}", e)),
+    }
+}
+
+/// Project cycle burn per chat from recorded per-feature averages, falling back
+/// to outcall pricing estimates for features with no history yet.
+fn project_cycles_per_chat(costs: &FeatureCosts, config: &AgentConfig, avg_prompt_len: u64, search_rate: f64) -> u64 {
+    let avg = |cycles: u64, calls: u64, estimate: u64| cycles.checked_div(calls).unwrap_or(estimate);
+
+    // LLM: one call per chat, plus a follow-up for every tool use.
+    // Scale by how far the requested prompt length is from the recorded average.
+    let llm_call = avg(costs.llm_cycles, costs.llm_calls,
+        outcall_cycles(MAX_PROMPT_BYTES as u64, config.max_response_bytes)) as f64;
+    let recorded_prompt = costs.prompt_bytes.checked_div(costs.chats).unwrap_or(avg_prompt_len);
+    let prompt_delta = (avg_prompt_len as f64 - recorded_prompt as f64) * OUTCALL_REQ_BYTE_CYCLES as f64;
+    let llm = (llm_call + prompt_delta).max(0.0) * (1.0 + search_rate);
+
+    // Search: everything a searching chat spent on lookups (parallel searches and fallbacks)
+    let search = search_rate * avg(costs.search_cycles, costs.search_chats, outcall_cycles(256, 6_000)) as f64;
+
+    // Scrapes follow URLs in messages — use the recorded per-chat rate
+    let scrape = costs.scrape_cycles.checked_div(costs.chats).unwrap_or(0) as f64;
+
+    // Compression runs every `compress_interval` messages (2 per chat)
+    let compress = if config.compress_interval > 0 {
+        let call = avg(costs.compress_cycles, costs.compress_calls, outcall_cycles(2_048, 3_072));
+        call as f64 * 2.0 / config.compress_interval as f64
+    } else {
+        0.0
+    };
+
+    (llm + search + scrape + compress) as u64
+}
+
+/// Project daily/monthly cycle burn and ICP cost for a hypothetical load.
+/// Costs one exchange rate canister call (1B cycles).
+#[ic_cdk::update]
+async fn simulate_costs(chats_per_day: u64, avg_prompt_len: u64, search_rate: f64) -> Result<CostProjection, String> {
+    require_controller()?;
+    if chats_per_day > 1_000_000 {
+        return Err("chats_per_day must be at most 1000000".into());
+    }
+    if avg_prompt_len as usize > MAX_PROMPT_BYTES {
+        return Err(format!("avg_prompt_len must be at most {}", MAX_PROMPT_BYTES));
+    }
+    if !(0.0..=1.0).contains(&search_rate) {
+        return Err("search_rate must be between 0 and 1".into());
+    }
+
+    let costs = FEATURE_COSTS.with(|c| c.borrow().get().clone());
+    let cycles_per_chat = project_cycles_per_chat(&costs, &get_config(), avg_prompt_len, search_rate);
+    let daily_cycles = cycles_per_chat.saturating_mul(chats_per_day);
+    let monthly_cycles = daily_cycles.saturating_mul(30);
+
+    // cycles → XDR (1T cycles = 1 XDR) → ICP at rate / 10^decimals XDR per ICP
+    let (rate, decimals) = fetch_icp_xdr_rate().await?;
+    let scale = 10u128.pow(decimals);
+    let to_e8s = |cycles: u64| (cycles as u128 * scale / (rate as u128 * 10_000)) as u64;
+
+    Ok(CostProjection {
+        cycles_per_chat,
+        daily_cycles,
+        monthly_cycles,
+        daily_icp_e8s: to_e8s(daily_cycles),
+        monthly_icp_e8s: to_e8s(monthly_cycles),
+        icp_xdr_rate: rate as f64 / scale as f64,
+        sample_chats: costs.chats,
+    })
+}
+
 // ═══════════════════════════════════════════════════════════════════════
 //  Background task queue
 // ═══════════════════════════════════════════════════════════════════════
@@ -2864,6 +3607,38 @@ fn get_queue_length() -> u64 {
 //  HTTP Gateway — serves a lightweight REST API
 // ═══════════════════════════════════════════════════════════════════════
 
+/// Issue a new bearer token for the read-only gateway routes, replacing any previous one.
+/// Only the hash is stored — the token is shown once.
+#[ic_cdk::update]
+async fn create_api_token() -> Result<String, String> {
+    require_controller()?;
+    let rand = ic_cdk::management_canister::raw_rand().await
+        .map_err(|e| format!("Failed to generate token: {:?}", e))?;
+    let token = format!("pico_{}", to_hex(&rand[..32]));
+    let hash = sha224(token.as_bytes());
+    API_TOKEN_HASH.with(|t| { let _ = t.borrow_mut().set(hash); });
+    Ok(token)
+}
+
+#[ic_cdk::update]
+fn revoke_api_token() -> Result<(), String> {
+    require_controller()?;
+    API_TOKEN_HASH.with(|t| { let _ = t.borrow_mut().set([0u8; 28]); });
+    Ok(())
+}
+
+/// Check the request's `Authorization: Bearer <token>` header against the stored hash.
+fn has_valid_api_token(req: &IngressHttpRequest) -> bool {
+    let stored = API_TOKEN_HASH.with(|t| *t.borrow().get());
+    if stored == [0u8; 28] {
+        return false;
+    }
+    req.headers.iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .filter_map(|(_, value)| value.strip_prefix("Bearer "))
+        .any(|token| sha224(token.trim().as_bytes()) == stored)
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct IngressHttpRequest {
     pub method: String,
@@ -2892,20 +3667,74 @@ fn json_response(status: u16, body: &str) -> IngressHttpResponse {
     }
 }
 
+/// Like `json_response`, for token-gated data that must not be cached by the gateway.
+fn private_json_response(status: u16, body: &str) -> IngressHttpResponse {
+    let mut resp = json_response(status, body);
+    resp.headers.push(("Cache-Control".into(), "no-store".into()));
+    resp
+}
+
+fn upgrade_response() -> IngressHttpResponse {
+    IngressHttpResponse {
+        status_code: 200,
+        headers: vec![],
+        body: vec![],
+        upgrade: Some(true),
+    }
+}
+
 fn get_path(url: &str) -> &str {
     url.split('?').next().unwrap_or("/")
 }
 
+/// Read-only, token-authenticated views for status pages, served from
+/// `http_request_update`. Only sanitized fields are exposed — no identity
+/// facts, priors, or config. Returns `None` for any other route.
+fn private_view(req: &IngressHttpRequest) -> Option<IngressHttpResponse> {
+    let path = get_path(&req.url);
+    if req.method != "GET" || (path != "/profile" && path != "/memory") {
+        return None;
+    }
+    if !has_valid_api_token(req) {
+        return Some(private_json_response(401, "{\"error\":\"invalid or missing API token\"}"));
+    }
+    let body = if path == "/profile" {
+        let p = USER_PROFILE.with(|p| p.borrow().get().clone());
+        let mut body = String::with_capacity(p.name.len() + p.avatar_url.len() + 64);
+        body.push_str("{\"name\":\"");
+        body.push_str(&json_escape(&p.name));
+        body.push_str("\",\"avatar_url\":\"");
+        body.push_str(&json_escape(&p.avatar_url));
+        body.push_str("\",\"updated_at\":");
+        body.push_str(&p.updated_at.to_string());
+        body.push('}');
+        body
+    } else {
+        let state = SESSION_NOTES.with(|s| s.borrow().get().clone());
+        let mut body = String::with_capacity(state.thread.len() + 48);
+        body.push_str("{\"thread\":\"");
+        body.push_str(&json_escape(&state.thread));
+        body.push_str("\",\"updated_at\":");
+        body.push_str(&state.updated_at.to_string());
+        body.push('}');
+        body
+    };
+    Some(private_json_response(200, &body))
+}
+
 #[ic_cdk::query]
 fn http_request(req: IngressHttpRequest) -> IngressHttpResponse {
     // Upgrade POSTs to update calls
     if req.method == "POST" {
-        return IngressHttpResponse {
-            status_code: 200,
-            headers: vec![],
-            body: vec![],
-            upgrade: Some(true),
-        };
+        return upgrade_response();
+    }
+
+    // CORS preflight for token-authenticated GETs
+    if req.method == "OPTIONS" {
+        let mut resp = json_response(204, "");
+        resp.headers.push(("Access-Control-Allow-Headers".into(), "Authorization".into()));
+        resp.headers.push(("Access-Control-Allow-Methods".into(), "GET, POST, OPTIONS".into()));
+        return resp;
     }
 
     match get_path(&req.url) {
@@ -2923,6 +3752,16 @@ fn http_request(req: IngressHttpRequest) -> IngressHttpResponse {
             body.push_str(&m.total_messages.to_string());
             body.push_str(",\"errors\":");
             body.push_str(&m.errors.to_string());
+            body.push_str(",\"provider_auth_errors\":");
+            body.push_str(&m.provider_auth_errors.to_string());
+            body.push_str(",\"provider_quota_errors\":");
+            body.push_str(&m.provider_quota_errors.to_string());
+            body.push_str(",\"provider_rate_limited\":");
+            body.push_str(&m.provider_rate_limited.to_string());
+            body.push_str(",\"provider_unavailable\":");
+            body.push_str(&m.provider_unavailable.to_string());
+            body.push_str(",\"provider_forbidden\":");
+            body.push_str(&m.provider_forbidden.to_string());
             body.push_str(",\"cycle_balance\":");
             body.push_str(&bal.to_string());
             body.push_str(",\"queue_depth\":");
@@ -2931,6 +3770,15 @@ fn http_request(req: IngressHttpRequest) -> IngressHttpResponse {
             json_response(200, &body)
         }
 
+        // Token-gated views are upgraded so the response goes through consensus.
+        // Bad tokens are turned away here without paying for an update call.
+        "/profile" | "/memory" => {
+            if !has_valid_api_token(&req) {
+                return private_json_response(401, "{\"error\":\"invalid or missing API token\"}");
+            }
+            upgrade_response()
+        }
+
         // /history and /config removed — use authenticated canister calls instead.
         _ => json_response(404, "{\"error\":\"not found\"}"),
     }
@@ -2938,6 +3786,11 @@ fn http_request(req: IngressHttpRequest) -> IngressHttpResponse {
 
 #[ic_cdk::update]
 async fn http_request_update(req: IngressHttpRequest) -> IngressHttpResponse {
+    // Upgraded token-gated GETs — the bearer token authenticates these, not the caller
+    if let Some(resp) = private_view(&req) {
+        return resp;
+    }
+
     if req.method != "POST" {
         return json_response(405, "{\"error\":\"method not allowed\"}");
     }
@@ -3003,11 +3856,14 @@ fn restore_counters() {
 #[ic_cdk::init]
 fn init() {
     restore_counters();
+    start_invite_sweep();
 }
 
 #[ic_cdk::post_upgrade]
 fn post_upgrade() {
     restore_counters();
+    sweep_invites();
+    start_invite_sweep();
     // Reset model to DeepSeek-V3 and update system prompt
     CONFIG.with(|c| {
         let mut cell = c.borrow_mut();